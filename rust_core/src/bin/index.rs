@@ -1,125 +1,592 @@
 // LanceDB Vector Indexer for MTG Cards using FastEmbed v5.x
 // Updated for LanceDB 0.22+ API changes
 
-use arrow_array::{FixedSizeListArray, Float32Array, RecordBatch, RecordBatchIterator, StringArray};
-use arrow_schema::{DataType, Field, Schema};
 use arrow_array::types::Float32Type;
-use fastembed::{TextEmbedding, InitOptions, EmbeddingModel};
+use arrow_array::{FixedSizeListArray, RecordBatch, RecordBatchIterator, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
 use lancedb::connect;
+use lancedb::index::scalar::FtsIndexBuilder;
+use lancedb::index::vector::IvfPqIndexBuilder;
+use lancedb::index::Index;
+use lancedb::table::Table;
+use lancedb::DistanceType;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::sync::Arc;
+use std::time::Instant;
+
+// MiniLM truncates at 256 tokens anyway, so there's no point carrying a huge
+// oracle text through the pipeline just to have the model throw most of it
+// away. ~4 chars/token is the standard rough estimate for English text.
+const CHARS_PER_TOKEN: usize = 4;
+const MAX_TOKENS: usize = 256;
+const CHAR_BUDGET: usize = MAX_TOKENS * CHARS_PER_TOKEN;
+
+// Flush whenever the buffer hits this many cards...
+const DEFAULT_BATCH_SIZE: usize = 256;
+// ...or this many approximate tokens, whichever comes first. This is
+// measured against each card's *pre-truncation* text (see the
+// `pretruncation_len` param on `PendingBatch::push`), not the
+// already-capped `combined_text` that gets embedded, so a run of unusually
+// long oracle texts counts at its full length and trips this sooner than
+// its capped length (at most `MAX_TOKENS` per card) would.
+const TOKEN_BUDGET: usize = 32_768;
+
+const DEFAULT_CACHE_PATH: &str = "data/embedding_cache";
+
+// `CHAR_BUDGET` is a byte budget, not a char count, and oracle text is full
+// of multi-byte characters (em dashes, the minus sign in loyalty costs like
+// "-3"). `String::truncate` panics if the cut point isn't on a char
+// boundary, so walk back to the nearest one instead of cutting blind.
+fn truncate_to_char_boundary(text: &mut String, max_bytes: usize) {
+    if text.len() <= max_bytes {
+        return;
+    }
+
+    let mut cut = max_bytes;
+    while cut > 0 && !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    text.truncate(cut);
+}
 
 #[derive(Debug, Deserialize)]
 struct CardJson {
+    id: String,
     name: String,
     oracle_text: String,
     type_line: String,
 }
 
+// --- CLI ---
+
+struct CliArgs {
+    cache_enabled: bool,
+    cache_path: String,
+    batch_size: usize,
+}
+
+fn parse_args() -> CliArgs {
+    let mut cache_enabled = true;
+    let mut cache_path = DEFAULT_CACHE_PATH.to_string();
+    let mut batch_size = DEFAULT_BATCH_SIZE;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-cache" => cache_enabled = false,
+            "--cache-path" => {
+                if let Some(path) = args.next() {
+                    cache_path = path;
+                }
+            }
+            "--batch-size" => {
+                if let Some(size) = args.next().and_then(|s| s.parse().ok()) {
+                    batch_size = size;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    CliArgs { cache_enabled, cache_path, batch_size }
+}
+
+// --- EMBEDDING CACHE ---
+// Content-addressed: keyed on sha256(combined_text) so an unchanged card
+// never gets re-embedded across runs, even after a full bulk re-download.
+
+fn hash_text(text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(text.as_bytes());
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn vector_to_bytes(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+fn bytes_to_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+        .collect()
+}
+
+struct EmbeddingCache {
+    db: Option<sled::Db>,
+}
+
+impl EmbeddingCache {
+    fn open(path: &str, enabled: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        if !enabled {
+            return Ok(Self { db: None });
+        }
+        Ok(Self { db: Some(sled::open(path)?) })
+    }
+
+    fn get(&self, hash: &str) -> Option<Vec<f32>> {
+        let db = self.db.as_ref()?;
+        let bytes = db.get(hash).ok()??;
+        Some(bytes_to_vector(&bytes))
+    }
+
+    fn put(&self, hash: &str, vector: &[f32]) {
+        if let Some(db) = &self.db {
+            let _ = db.insert(hash, vector_to_bytes(vector));
+        }
+    }
+}
+
+// --- SCHEMA ---
+
+fn arrow_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("type_line", DataType::Utf8, false),
+        Field::new("oracle_text", DataType::Utf8, false),
+        Field::new(
+            "vector",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 384),
+            false,
+        ),
+    ]))
+}
+
+// --- BATCHING ---
+
+// One card waiting to be flushed. `vector` is already populated from the
+// cache on a hit; on a miss it stays `None` until `embed_batch` fills it in.
+struct PendingRow {
+    id: String,
+    name: String,
+    type_line: String,
+    oracle_text: String,
+    hash: String,
+    combined_text: String,
+    vector: Option<Vec<f32>>,
+}
+
+// Accumulates parsed cards until it's time to embed and flush them.
+struct PendingBatch {
+    rows: Vec<PendingRow>,
+    approx_tokens: usize,
+    cache_hits: usize,
+    batch_size: usize,
+}
+
+impl PendingBatch {
+    fn new(batch_size: usize) -> Self {
+        Self { rows: Vec::new(), approx_tokens: 0, cache_hits: 0, batch_size }
+    }
+
+    // `pretruncation_len` is the byte length of `combined_text` *before* it
+    // was capped to `CHAR_BUDGET` — see the comment on `TOKEN_BUDGET` for why
+    // the token count is measured pre-truncation.
+    fn push(
+        &mut self,
+        card: CardJson,
+        combined_text: String,
+        pretruncation_len: usize,
+        hash: String,
+        cached_vector: Option<Vec<f32>>,
+    ) {
+        self.approx_tokens += pretruncation_len / CHARS_PER_TOKEN;
+        if cached_vector.is_some() {
+            self.cache_hits += 1;
+        }
+        self.rows.push(PendingRow {
+            id: card.id,
+            name: card.name,
+            type_line: card.type_line,
+            oracle_text: card.oracle_text,
+            hash,
+            combined_text,
+            vector: cached_vector,
+        });
+    }
+
+    fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn should_flush(&self) -> bool {
+        self.len() >= self.batch_size || self.approx_tokens >= TOKEN_BUDGET
+    }
+}
+
+// Embeds whatever rows are still missing a vector (cache misses), stores the
+// freshly computed vectors back into the cache, and turns the whole batch
+// into a single Arrow RecordBatch.
+fn embed_batch(
+    model: &mut TextEmbedding,
+    cache: &EmbeddingCache,
+    schema: &Arc<Schema>,
+    mut batch: PendingBatch,
+) -> Result<RecordBatch, Box<dyn std::error::Error>> {
+    let miss_indices: Vec<usize> = batch
+        .rows
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| row.vector.is_none())
+        .map(|(i, _)| i)
+        .collect();
+
+    if !miss_indices.is_empty() {
+        let to_embed: Vec<String> = miss_indices.iter().map(|&i| batch.rows[i].combined_text.clone()).collect();
+        let embeddings = model.embed(to_embed, Some(DEFAULT_BATCH_SIZE))?;
+
+        for (&i, vector) in miss_indices.iter().zip(embeddings.into_iter()) {
+            cache.put(&batch.rows[i].hash, &vector);
+            batch.rows[i].vector = Some(vector);
+        }
+    }
+
+    let row_count = batch.rows.len();
+    let mut ids = Vec::with_capacity(row_count);
+    let mut names = Vec::with_capacity(row_count);
+    let mut types = Vec::with_capacity(row_count);
+    let mut texts = Vec::with_capacity(row_count);
+    let mut flattened: Vec<f32> = Vec::with_capacity(row_count * 384);
+
+    for row in batch.rows {
+        ids.push(row.id);
+        names.push(row.name);
+        types.push(row.type_line);
+        texts.push(row.oracle_text);
+        flattened.extend(row.vector.expect("every row has a vector by this point"));
+    }
+
+    Ok(RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(StringArray::from(ids)),
+            Arc::new(StringArray::from(names)),
+            Arc::new(StringArray::from(types)),
+            Arc::new(StringArray::from(texts)),
+            Arc::new(FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                (0..row_count).map(|i| Some(flattened[i * 384..(i + 1) * 384].to_vec().into_iter().map(Some))),
+                384,
+            )),
+        ],
+    )?)
+}
+
+// Writes one flushed RecordBatch to LanceDB. If the `cards` table doesn't
+// exist yet, the first flush creates it; every flush after that — including
+// the first one on a re-run against an existing table — goes through
+// `merge_insert` keyed on `id`, so every row whose `id` already exists is
+// overwritten with this run's values, brand-new rows are inserted, and rows
+// not in this batch are left alone.
+async fn write_chunk(
+    db: &lancedb::Connection,
+    table: &mut Option<Table>,
+    schema: &Arc<Schema>,
+    batch: RecordBatch,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+
+    match table {
+        Some(t) => {
+            t.merge_insert(&["id"])
+                .when_matched_update_all(None)
+                .when_not_matched_insert_all()
+                .execute(Box::new(batches))
+                .await?;
+        }
+        None => {
+            let new_table = db.create_table("cards", batches).execute().await?;
+            *table = Some(new_table);
+        }
+    }
+
+    Ok(())
+}
+
+// Deletes any row whose `id` wasn't seen in this run's `processed_cards.jsonl`
+// — i.e. cards Scryfall has removed or renamed since the last index. Only
+// safe to run after a full, uncapped pass over the file.
+async fn prune_stale_rows(
+    table: &Table,
+    seen_ids: &HashSet<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if seen_ids.is_empty() {
+        return Ok(());
+    }
+
+    // Fine at tens of thousands of ids; a full Scryfall bulk dump is ~30k
+    // rows. At much larger scale this would want an anti-join against a temp
+    // table instead of one giant NOT IN (...) filter string.
+    let quoted: Vec<String> = seen_ids.iter().map(|id| format!("'{}'", id.replace('\'', "''"))).collect();
+    let filter = format!("id NOT IN ({})", quoted.join(","));
+    table.delete(&filter).await?;
+    Ok(())
+}
+
+// Number of sub-vectors for IVF_PQ — must divide the embedding dimension
+// (384). 96 gives each sub-vector 4 floats, a reasonable quantization
+// granularity for MiniLM embeddings.
+const PQ_SUB_VECTORS: usize = 96;
+
+// Builds (or rebuilds) the ANN index on the `vector` column. MiniLM
+// embeddings are L2-normalized, so cosine distance is what actually matches
+// semantic similarity — an exhaustive scan doesn't care, but IVF_PQ's
+// clustering does. Partition count on the order of sqrt(num_rows) is the
+// standard IVF rule of thumb: too few partitions means little pruning, too
+// many means mostly-empty partitions and wasted probes.
+async fn build_ann_index(table: &Table) -> Result<(), Box<dyn std::error::Error>> {
+    let num_rows = table.count_rows(None).await?;
+    if num_rows == 0 {
+        return Ok(());
+    }
+
+    let num_partitions = (num_rows as f64).sqrt().round().max(1.0) as u32;
+
+    table
+        .create_index(
+            &["vector"],
+            Index::IvfPq(
+                IvfPqIndexBuilder::default()
+                    .distance_type(DistanceType::Cosine)
+                    .num_partitions(num_partitions)
+                    .num_sub_vectors(PQ_SUB_VECTORS as u32),
+            ),
+        )
+        .execute()
+        .await?;
+
+    println!(
+        ">>> Built IVF_PQ index on 'vector' ({} partitions, {} sub-vectors, cosine distance) over {} rows.",
+        num_partitions, PQ_SUB_VECTORS, num_rows
+    );
+    Ok(())
+}
+
+// Builds the BM25 full-text index over `name` and `oracle_text` so
+// `search_cards` can run a keyword query alongside the vector query and fuse
+// the two with RRF — a literal search like "Lightning Bolt" or "trample"
+// should rank the exact hit highly even when its embedding isn't the
+// closest neighbor.
+async fn build_fts_index(table: &Table) -> Result<(), Box<dyn std::error::Error>> {
+    table
+        .create_index(&["name", "oracle_text"], Index::FTS(FtsIndexBuilder::default()))
+        .execute()
+        .await?;
+
+    println!(">>> Built BM25 full-text index on 'name' and 'oracle_text'.");
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!(">>> Initializing Vector Indexer (Modern Stack)...");
 
+    let args = parse_args();
+    let cache = EmbeddingCache::open(&args.cache_path, args.cache_enabled)?;
+    if args.cache_enabled {
+        println!(">>> Embedding cache: {}", args.cache_path);
+    } else {
+        println!(">>> Embedding cache: disabled (--no-cache)");
+    }
+
     // 1. Setup Embedding Model (FastEmbed v5.x)
     // The API changed from a struct literal to a builder pattern
     let mut model = TextEmbedding::try_new(
-        InitOptions::new(EmbeddingModel::AllMiniLML6V2)
-            .with_show_download_progress(true)
+        InitOptions::new(EmbeddingModel::AllMiniLML6V2).with_show_download_progress(true),
     )?;
 
     // 2. Connect to LanceDB
     // 0.22+ uses 'execute()' pattern for connections
     let uri = "data/lancedb";
     let db = connect(uri).execute().await?;
-    
+    let schema = arrow_schema();
+    // If `cards` already exists from a previous run, open it so every flush
+    // goes through merge_insert instead of clobbering it with create_table.
+    let mut table: Option<Table> = db.open_table("cards").execute().await.ok();
+
     // 3. Read Data
     println!(">>> Reading processed_cards.jsonl...");
     let file = File::open("processed_cards.jsonl")?;
     let reader = BufReader::new(file);
 
-    let mut names = Vec::new();
-    let mut texts = Vec::new(); // For display
-    let mut types = Vec::new();
-    let mut embeddings = Vec::new();
+    let mut pending = PendingBatch::new(args.batch_size);
+    let mut total_indexed = 0usize;
+    let mut total_cache_hits = 0usize;
+    let mut seen_ids: HashSet<String> = HashSet::new();
+    let mut hit_test_cap = false;
+    let start = Instant::now();
 
-    let mut count = 0;
-    
     for line in reader.lines() {
         let line = line?;
         // Robustness: Ignore empty lines or parse errors
-        if let Ok(card) = serde_json::from_str::<CardJson>(&line) {
-            // Skip cards with no text to save space
-            if card.oracle_text.is_empty() { continue; }
-
-            names.push(card.name.clone());
-            texts.push(card.oracle_text.clone());
-            types.push(card.type_line.clone());
-
-            // Combine fields for richer semantic search
-            let combined_text = format!("{} - {} \n {}", card.name, card.type_line, card.oracle_text);
-            
-            // Generate Vector
-            let vector = model.embed(vec![combined_text], None)?;
-            embeddings.push(vector[0].clone());
-
-            count += 1;
-            if count % 100 == 0 {
-                print!("\rIndexing: {} cards...", count);
-            }
-            
-            // Limit for testing (remove this line for full import)
-            if count >= 1000 { break; } 
+        let Ok(card) = serde_json::from_str::<CardJson>(&line) else {
+            continue;
+        };
+
+        // Skip cards with no text to save space
+        if card.oracle_text.is_empty() {
+            continue;
+        }
+
+        // Combine fields for richer semantic search. Truncate here, at parse
+        // time, so a pathologically huge oracle text never makes it to the
+        // model — MiniLM would just truncate at 256 tokens anyway.
+        let mut combined_text = format!("{} - {} \n {}", card.name, card.type_line, card.oracle_text);
+        let pretruncation_len = combined_text.len();
+        truncate_to_char_boundary(&mut combined_text, CHAR_BUDGET);
+
+        let hash = hash_text(&combined_text);
+        let cached_vector = cache.get(&hash);
+        seen_ids.insert(card.id.clone());
+
+        pending.push(card, combined_text, pretruncation_len, hash, cached_vector);
+
+        // Limit for testing (remove this line for full import)
+        if total_indexed + pending.len() >= 1000 {
+            let flushed = std::mem::replace(&mut pending, PendingBatch::new(args.batch_size));
+            let flush_size = flushed.len();
+            total_cache_hits += flushed.cache_hits;
+            let batch = embed_batch(&mut model, &cache, &schema, flushed)?;
+            write_chunk(&db, &mut table, &schema, batch).await?;
+            total_indexed += flush_size;
+            hit_test_cap = true;
+            println!("\rFlushed {} cards — {} total (test cap reached)", flush_size, total_indexed);
+            break;
+        }
+
+        if pending.should_flush() {
+            let flushed = std::mem::replace(&mut pending, PendingBatch::new(args.batch_size));
+            let flush_size = flushed.len();
+            let cache_hits = flushed.cache_hits;
+            let flush_start = Instant::now();
+
+            let batch = embed_batch(&mut model, &cache, &schema, flushed)?;
+            write_chunk(&db, &mut table, &schema, batch).await?;
+
+            total_indexed += flush_size;
+            total_cache_hits += cache_hits;
+            let elapsed = flush_start.elapsed().as_secs_f64();
+            let rate = flush_size as f64 / elapsed.max(f64::EPSILON);
+            println!(
+                "\rFlushed {} cards ({} cached, {:.1} cards/sec) — {} total",
+                flush_size, cache_hits, rate, total_indexed
+            );
         }
     }
 
-    println!("\n>>> Converting to Arrow format...");
+    // Flush whatever's left under a full batch.
+    if pending.len() > 0 {
+        let flush_size = pending.len();
+        let cache_hits = pending.cache_hits;
+        let flush_start = Instant::now();
 
-    // 4. Create Arrow Schema & Batch
-    // We use lancedb::arrow types to ensure version compatibility
-    let schema = Arc::new(Schema::new(vec![
-        Field::new("name", DataType::Utf8, false),
-        Field::new("type_line", DataType::Utf8, false),
-        Field::new("oracle_text", DataType::Utf8, false),
-        Field::new("vector", DataType::FixedSizeList(
-            Arc::new(Field::new("item", DataType::Float32, true)),
-            384 // Dimension size for MiniLM
-        ), false),
-    ]));
-
-    let total_rows = names.len();
-    
-    // Flatten embeddings for the FixedSizeListArray
-    let flattened_embeddings: Vec<f32> = embeddings.into_iter().flatten().collect();
-    
-    let batch = RecordBatch::try_new(
-        schema.clone(),
-        vec![
-            Arc::new(StringArray::from(names)),
-            Arc::new(StringArray::from(types)),
-            Arc::new(StringArray::from(texts)),
-            Arc::new(FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
-                // We reconstruct the list array from the flattened data
-                (0..total_rows).map(|i| {
-                    Some(flattened_embeddings[i*384..(i+1)*384].to_vec().into_iter().map(Some))
-                }),
-                384
-            )),
-        ],
-    )?;
+        let batch = embed_batch(&mut model, &cache, &schema, pending)?;
+        write_chunk(&db, &mut table, &schema, batch).await?;
 
-    // 5. Write to DB
-    // LanceDB 0.22 expects an Iterator of batches, not a single batch
-    let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        total_indexed += flush_size;
+        total_cache_hits += cache_hits;
+        let elapsed = flush_start.elapsed().as_secs_f64();
+        let rate = flush_size as f64 / elapsed.max(f64::EPSILON);
+        println!(
+            "\rFlushed {} cards ({} cached, {:.1} cards/sec) — {} total",
+            flush_size, cache_hits, rate, total_indexed
+        );
+    }
 
-    println!(">>> Writing to LanceDB...");
-    
-    // 'create_table' now returns a builder, we call execute()
-    db.create_table("cards", batches)
-        .execute()
-        .await?;
+    if let Some(t) = &table {
+        if hit_test_cap {
+            println!(">>> Skipping stale-row pruning: test cap reached, this wasn't a full pass.");
+        } else {
+            prune_stale_rows(t, &seen_ids).await?;
+            println!(">>> Pruned rows no longer present in processed_cards.jsonl.");
+        }
 
-    println!(">>> Indexing Complete. Table 'cards' created at ./data/lancedb");
+        build_ann_index(t).await?;
+        build_fts_index(t).await?;
+    }
+
+    let total_elapsed = start.elapsed().as_secs_f64();
+    println!(
+        ">>> Indexing Complete. {} cards indexed ({} from cache) in {:.1}s ({:.1} cards/sec overall).",
+        total_indexed,
+        total_cache_hits,
+        total_elapsed,
+        total_indexed as f64 / total_elapsed.max(f64::EPSILON)
+    );
+    println!(">>> Table 'cards' ready at ./data/lancedb");
     Ok(())
 }
+
+// --- TESTS ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_to_char_boundary_does_not_panic_on_multibyte_cut() {
+        // The minus sign straddles byte 1023/1024 of a naive cut, which is
+        // exactly what used to panic before `truncate_to_char_boundary`.
+        let mut text = "a".repeat(1023) + "\u{2212}" + "b";
+        truncate_to_char_boundary(&mut text, 1024);
+
+        assert!(text.len() <= 1024);
+        assert!(text.is_char_boundary(text.len()));
+    }
+
+    #[test]
+    fn test_truncate_to_char_boundary_leaves_short_strings_alone() {
+        let mut text = "Lightning Bolt".to_string();
+        truncate_to_char_boundary(&mut text, 1024);
+        assert_eq!(text, "Lightning Bolt");
+    }
+
+    #[test]
+    fn test_vector_bytes_round_trip() {
+        let vector: Vec<f32> = vec![0.0, -1.5, 3.25, f32::MIN, f32::MAX];
+        let bytes = vector_to_bytes(&vector);
+        assert_eq!(bytes_to_vector(&bytes), vector);
+    }
+
+    #[test]
+    fn test_hash_text_is_stable_and_content_addressed() {
+        assert_eq!(hash_text("Lightning Bolt"), hash_text("Lightning Bolt"));
+        assert_ne!(hash_text("Lightning Bolt"), hash_text("Lightning Strike"));
+    }
+
+    fn card(id: &str) -> CardJson {
+        CardJson {
+            id: id.to_string(),
+            name: id.to_string(),
+            oracle_text: String::new(),
+            type_line: "Instant".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_should_flush_trips_on_token_budget_before_batch_size() {
+        // A handful of very long oracle texts should be able to trip the
+        // token budget well before `DEFAULT_BATCH_SIZE` cards accumulate.
+        let mut pending = PendingBatch::new(DEFAULT_BATCH_SIZE);
+        let huge_pretruncation_len = TOKEN_BUDGET * CHARS_PER_TOKEN;
+        pending.push(card("a"), "a".to_string(), huge_pretruncation_len, hash_text("a"), None);
+
+        assert!(pending.len() < DEFAULT_BATCH_SIZE);
+        assert!(pending.should_flush());
+    }
+
+    #[test]
+    fn test_should_flush_respects_configured_batch_size() {
+        let mut pending = PendingBatch::new(2);
+        pending.push(card("a"), "a".to_string(), 1, hash_text("a"), None);
+        assert!(!pending.should_flush());
+
+        pending.push(card("b"), "b".to_string(), 1, hash_text("b"), None);
+        assert!(pending.should_flush());
+    }
+}
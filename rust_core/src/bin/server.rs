@@ -0,0 +1,112 @@
+// rust_core/src/bin/server.rs
+// Axum HTTP/SSE front-end for the Judge and the Librarian. Wraps the exact
+// same logic the PyO3 bindings in lib.rs expose, so a UI or any other
+// language can drive this engine over plain HTTP instead of embedding a
+// Python process.
+
+use axum::extract::Query;
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::response::sse::{Event, Sse};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::Router;
+use futures::stream::{self, Stream};
+use serde::Deserialize;
+use serde_json::json;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+const DEFAULT_ALLOWED_ORIGIN: &str = "http://localhost:3000";
+
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    q: String,
+    limit: Option<usize>,
+    nprobes: Option<usize>,
+    search_mode: Option<String>,
+}
+
+// --- CLI ---
+
+struct CliArgs {
+    allowed_origins: Vec<HeaderValue>,
+}
+
+fn parse_args() -> CliArgs {
+    let mut allowed_origins = Vec::new();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--allow-origin" {
+            if let Some(origin) = args.next().and_then(|s| s.parse().ok()) {
+                allowed_origins.push(origin);
+            }
+        }
+    }
+
+    if allowed_origins.is_empty() {
+        allowed_origins.push(HeaderValue::from_static(DEFAULT_ALLOWED_ORIGIN));
+    }
+
+    CliArgs { allowed_origins }
+}
+
+// POST /judge — takes the same GameState JSON the PyO3 `check_board_state`
+// takes, and returns the same ruling list.
+async fn judge_handler(body: String) -> impl IntoResponse {
+    let result = rust_core::judge(&body);
+    (StatusCode::OK, [(header::CONTENT_TYPE, "application/json")], result)
+}
+
+// GET /search?q=...&limit=... — embeds the query and runs the same LanceDB
+// search as the PyO3 `search_cards`, but streams one card per SSE event
+// instead of returning a single JSON blob, so a UI can render results as
+// they arrive.
+//
+// Unlike the PyO3 `search_cards` binding, this endpoint doesn't take a
+// free-text `where` SQL filter: that binding is only ever reached from a
+// trusted, same-process caller, but this one is open to any HTTP client, and
+// forwarding an arbitrary filter straight into LanceDB's `.only_if()` would
+// be a SQL-filter injection surface.
+async fn search_handler(
+    Query(params): Query<SearchParams>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let limit = params.limit.unwrap_or(5);
+    let mode = params.search_mode.unwrap_or_else(|| "hybrid".to_string());
+
+    let cards = rust_core::run_search(params.q, limit, None, params.nprobes, &mode)
+        .await
+        .unwrap_or_else(|e| vec![json!({ "status": "error", "message": e })]);
+
+    let events = cards
+        .into_iter()
+        .map(|card| Ok(Event::default().json_data(card).expect("card is valid JSON")));
+
+    Sse::new(stream::iter(events))
+}
+
+#[tokio::main]
+async fn main() {
+    let args = parse_args();
+
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(args.allowed_origins))
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE]);
+
+    // SSE bodies must flush one event at a time; a compressing encoder
+    // buffers across the stream, so only /judge (a single JSON response)
+    // gets compression, not /search.
+    let judge_routes = Router::new().route("/judge", post(judge_handler)).layer(CompressionLayer::new());
+    let search_routes = Router::new().route("/search", get(search_handler));
+
+    let app = Router::new().merge(judge_routes).merge(search_routes).layer(cors);
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
+    println!(">>> Serving the Judge (POST /judge) and the Librarian (GET /search) on {}", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr).await.expect("Failed to bind address");
+    axum::serve(listener, app).await.expect("Server crashed");
+}
@@ -6,6 +6,10 @@
 // This connects the wires. It deserializes the JSON string from Python,
 // hands it to the Judge in rules.rs, converts the Ruling enum back
 // to a string, and returns it.
+//
+// The core logic below (`judge`, `embed_query`, `run_search`) is plain Rust
+// with no PyO3 in it, so the `bin/server.rs` HTTP front-end can call the
+// exact same code the Python bindings do, instead of duplicating it.
 
 use pyo3::prelude::*;
 use serde_json::json;
@@ -15,8 +19,11 @@ use std::sync::{OnceLock, Mutex};
 use arrow_array::{RecordBatch, StringArray};
 use fastembed::{TextEmbedding, InitOptions, EmbeddingModel};
 use lancedb::connect;
-use lancedb::query::{ExecutableQuery, QueryBase}; // Import the trait for .limit()
+use lancedb::query::{ExecutableQuery, FullTextSearchQuery, QueryBase}; // Import the trait for .limit()
+use lancedb::table::Table;
+use lancedb::DistanceType;
 use futures::TryStreamExt;
+use std::collections::HashMap;
 use tokio::runtime::Runtime; // Import Runtime
 
 mod models;
@@ -30,7 +37,7 @@ use rules::{Judge, Ruling};
 // 1. Embedding Model (Heavy: ~30MB)
 static MODEL: OnceLock<Mutex<TextEmbedding>> = OnceLock::new();
 
-fn get_model() -> &'static Mutex<TextEmbedding> {
+pub(crate) fn get_model() -> &'static Mutex<TextEmbedding> {
     MODEL.get_or_init(|| {
         Mutex::new(TextEmbedding::try_new(
             InitOptions::new(EmbeddingModel::AllMiniLML6V2)
@@ -43,7 +50,7 @@ fn get_model() -> &'static Mutex<TextEmbedding> {
 // OPTIMIZATION: Created once, reused for all async calls.
 static RUNTIME: OnceLock<Runtime> = OnceLock::new();
 
-fn get_runtime() -> &'static Runtime {
+pub(crate) fn get_runtime() -> &'static Runtime {
     RUNTIME.get_or_init(|| {
         Runtime::new().expect("Failed to create Tokio Runtime")
     })
@@ -63,16 +70,19 @@ fn get_string_column<'a>(batch: &'a RecordBatch, col_name: &str) -> Result<&'a S
         .ok_or_else(|| format!("Column '{}' is not a StringArray (Type Mismatch)", col_name))
 }
 
-
 // --- THE JUDGE (Rule Engine) ---
-#[pyfunction]
-fn check_board_state(json_payload: String) -> PyResult<String> {
-    let state: GameState = match serde_json::from_str(&json_payload) {
+
+/// Runs the rules engine against a raw `GameState` JSON payload and returns
+/// the ruling list as a JSON string. Parse errors are reported in the JSON
+/// body rather than as an `Err`, since every caller (PyO3, HTTP) just wants
+/// a response to hand back, not a Rust error to unwrap.
+pub fn judge(json_payload: &str) -> String {
+    let state: GameState = match serde_json::from_str(json_payload) {
         Ok(s) => s,
-        Err(e) => return Ok(json!({
+        Err(e) => return json!({
             "status": "error",
             "message": format!("JSON Parse Error: {}", e)
-        }).to_string()),
+        }).to_string(),
     };
 
     let rulings = Judge::assess_state(&state);
@@ -83,85 +93,371 @@ fn check_board_state(json_payload: String) -> PyResult<String> {
         Ruling::StateBasedAction(action) => json!({ "status": "sba_trigger", "action": action }),
     }).collect::<Vec<_>>();
 
-    Ok(serde_json::to_string(&response).unwrap())
+    serde_json::to_string(&response).unwrap()
 }
 
-// --- THE LIBRARIAN (Vector Search) ---
 #[pyfunction]
-fn search_cards(query: String, limit: Option<usize>, where_clause: Option<String>) -> PyResult<String> {
-    let limit = limit.unwrap_or(5);
+fn check_board_state(json_payload: String) -> PyResult<String> {
+    Ok(judge(&json_payload))
+}
+
+// --- THE LIBRARIAN (Vector Search) ---
 
-    // 1. Generate Embedding
+/// Embeds a query string using the shared `MODEL` singleton.
+pub fn embed_query(query: String) -> Result<Vec<f32>, String> {
     let model = get_model();
     let mut model_lock = model.lock().unwrap();
-    let query_embedding = model_lock.embed(vec![query], None)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
-    
-    let query_vector = query_embedding[0].clone();
+    let embedding = model_lock.embed(vec![query], None).map_err(|e| e.to_string())?;
+    Ok(embedding[0].clone())
+}
 
-    // 2. Run Async Search using Global Runtime
-    let rt = get_runtime(); // <--- OPTIMIZATION: Use the static runtime
-    
-    let results_json = rt.block_on(async {
-        // Connect
-        let uri = "data/lancedb";
-        let db = connect(uri).execute().await
-            .map_err(|e| format!("DB Connection Failed: {}", e))?;
-        
-        let table = db.open_table("cards").execute().await
-            .map_err(|e| format!("Table Open Failed: {}", e))?;
-
-        // Initialize Query Builder
-        let mut query_builder = table.query()
-            .nearest_to(query_vector)
-            .map_err(|e| format!("Invalid Query Vector: {}", e))?;
-
-        // Apply Hybrid Filter
-        if let Some(sql) = where_clause {
-            // "filter" accepts standard SQL strings like "type_line LIKE '%Creature%'"
-            query_builder = query_builder.only_if(sql);
+// RRF's rank-damping constant. 60 is the value from the original Cormack et
+// al. paper and is what most hybrid search implementations default to.
+const RRF_K: f64 = 60.0;
+
+// How many candidates to pull from each ranked list before fusing. Wider
+// than `limit` so RRF has enough overlap to actually matter — if both lists
+// were truncated to exactly `limit`, a good keyword hit that rates as the
+// 6th-closest vector match would never get the chance to be fused in.
+fn candidate_pool_size(limit: usize) -> usize {
+    (limit * 4).max(50)
+}
+
+// Pulls `id`/`name`/`type_line`/`oracle_text` out of a LanceDB result set and
+// returns them in ranked order, one JSON object per row, without any score
+// attached yet — that's `reciprocal_rank_fusion`'s job.
+fn extract_cards(results: Vec<RecordBatch>) -> Result<Vec<serde_json::Value>, String> {
+    let mut cards = Vec::new();
+
+    for batch in results {
+        // OPTIMIZATION: Use the safer helper function with '?'
+        // If the schema is wrong, this returns Err(String) immediately
+        let ids = get_string_column(&batch, "id")?;
+        let names = get_string_column(&batch, "name")?;
+        let texts = get_string_column(&batch, "oracle_text")?;
+        let types = get_string_column(&batch, "type_line")?;
+
+        for i in 0..batch.num_rows() {
+            cards.push(json!({
+                "id": ids.value(i),
+                "name": names.value(i),
+                "type": types.value(i),
+                "text": texts.value(i)
+            }));
         }
+    }
 
-        let results = query_builder
-            .limit(limit)
-            .execute()
-            .await
-            .map_err(|e| format!("Query Execution Failed: {}", e))?
-            .try_collect::<Vec<_>>()
-            .await
-            .map_err(|e| format!("Stream Collection Failed: {}", e))?;
-
-        // Extract Data
-        let mut found_cards = Vec::new();
-
-        for batch in results {
-            // OPTIMIZATION: Use the safer helper function with '?'
-            // If the schema is wrong, this returns Err(String) immediately
-            let names = get_string_column(&batch, "name")?;
-            let texts = get_string_column(&batch, "oracle_text")?;
-            let types = get_string_column(&batch, "type_line")?;
-
-            for i in 0..batch.num_rows() {
-                found_cards.push(json!({
-                    "name": names.value(i),
-                    "type": types.value(i),
-                    "text": texts.value(i)
-                }));
-            }
+    Ok(cards)
+}
+
+/// Runs the LanceDB nearest-neighbor query for an already-embedded vector and
+/// returns the matching cards in ranked order.
+///
+/// `nprobes` trades recall for latency against the IVF_PQ index built by the
+/// indexer: more probed partitions means better recall at higher latency.
+/// `None` leaves LanceDB's default.
+async fn vector_candidates(
+    table: &Table,
+    query_vector: Vec<f32>,
+    where_clause: Option<&str>,
+    nprobes: Option<usize>,
+    pool_size: usize,
+) -> Result<Vec<serde_json::Value>, String> {
+    // MiniLM embeddings are L2-normalized, so cosine distance is what
+    // actually lines up with semantic similarity — this has to match the
+    // distance type the indexer built the IVF_PQ index with.
+    let mut query_builder = table.query()
+        .nearest_to(query_vector)
+        .map_err(|e| format!("Invalid Query Vector: {}", e))?
+        .distance_type(DistanceType::Cosine);
+
+    if let Some(n) = nprobes {
+        query_builder = query_builder.nprobes(n);
+    }
+
+    if let Some(sql) = where_clause {
+        // "filter" accepts standard SQL strings like "type_line LIKE '%Creature%'"
+        query_builder = query_builder.only_if(sql);
+    }
+
+    let results = query_builder
+        .limit(pool_size)
+        .execute()
+        .await
+        .map_err(|e| format!("Vector Query Execution Failed: {}", e))?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(|e| format!("Stream Collection Failed: {}", e))?;
+
+    extract_cards(results)
+}
+
+/// Runs a BM25 full-text query over the `name`/`oracle_text` index built by
+/// the indexer and returns the matching cards in ranked order. This is what
+/// catches literal hits like an exact card name or keyword ability that a
+/// semantic query can miss.
+async fn fts_candidates(
+    table: &Table,
+    query_text: &str,
+    where_clause: Option<&str>,
+    pool_size: usize,
+) -> Result<Vec<serde_json::Value>, String> {
+    let mut query_builder = table.query().full_text_search(FullTextSearchQuery::new(query_text.to_string()));
+
+    if let Some(sql) = where_clause {
+        query_builder = query_builder.only_if(sql);
+    }
+
+    let results = query_builder
+        .limit(pool_size)
+        .execute()
+        .await
+        .map_err(|e| format!("Full-Text Query Execution Failed: {}", e))?
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(|e| format!("Stream Collection Failed: {}", e))?;
+
+    extract_cards(results)
+}
+
+/// Fuses any number of ranked candidate lists with Reciprocal Rank Fusion:
+/// for each document, `score = Σ 1/(k + rank)` across every list it appears
+/// in (rank starting at 1). A document present in only one list still gets
+/// its single contribution. Sorts descending and truncates to `limit`.
+fn reciprocal_rank_fusion(lists: Vec<Vec<serde_json::Value>>, limit: usize) -> Vec<serde_json::Value> {
+    let mut scores: HashMap<String, f64> = HashMap::new();
+    let mut cards: HashMap<String, serde_json::Value> = HashMap::new();
+
+    for list in lists {
+        for (rank, card) in list.into_iter().enumerate() {
+            let id = card.get("id").and_then(serde_json::Value::as_str).unwrap_or_default().to_string();
+            *scores.entry(id.clone()).or_insert(0.0) += 1.0 / (RRF_K + (rank + 1) as f64);
+            cards.entry(id).or_insert(card);
         }
+    }
+
+    let mut fused: Vec<(f64, serde_json::Value)> = cards
+        .into_iter()
+        .map(|(id, mut card)| {
+            let score = scores[&id];
+            card["score"] = json!(score);
+            (score, card)
+        })
+        .collect();
+
+    fused.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    fused.into_iter().take(limit).map(|(_, card)| card).collect()
+}
+
+/// Runs `search_cards`'s full pipeline: embed (if needed), query LanceDB in
+/// one or both modes, and fuse with RRF. `mode` is `"vector"`, `"fts"`, or
+/// `"hybrid"` (the default — anything else falls back to hybrid).
+pub async fn run_search(
+    query_text: String,
+    limit: usize,
+    where_clause: Option<String>,
+    nprobes: Option<usize>,
+    mode: &str,
+) -> Result<Vec<serde_json::Value>, String> {
+    let uri = "data/lancedb";
+    let db = connect(uri).execute().await
+        .map_err(|e| format!("DB Connection Failed: {}", e))?;
 
-        Ok::<String, String>(serde_json::to_string(&found_cards).unwrap())
-    });
+    let table = db.open_table("cards").execute().await
+        .map_err(|e| format!("Table Open Failed: {}", e))?;
 
-    match results_json {
-        Ok(json_str) => Ok(json_str),
+    let want_vector = mode != "fts";
+    let want_fts = mode != "vector";
+    let pool_size = candidate_pool_size(limit);
+
+    let mut lists = Vec::new();
+
+    if want_vector {
+        let query_vector = embed_query(query_text.clone())?;
+        lists.push(vector_candidates(&table, query_vector, where_clause.as_deref(), nprobes, pool_size).await?);
+    }
+
+    if want_fts {
+        lists.push(fts_candidates(&table, &query_text, where_clause.as_deref(), pool_size).await?);
+    }
+
+    Ok(reciprocal_rank_fusion(lists, limit))
+}
+
+#[pyfunction]
+#[pyo3(signature = (query, limit=None, where_clause=None, nprobes=None, search_mode=None))]
+fn search_cards(
+    query: String,
+    limit: Option<usize>,
+    where_clause: Option<String>,
+    nprobes: Option<usize>,
+    search_mode: Option<String>,
+) -> PyResult<String> {
+    let limit = limit.unwrap_or(5);
+    let mode = search_mode.unwrap_or_else(|| "hybrid".to_string());
+
+    // Run Async Search using Global Runtime
+    let rt = get_runtime(); // <--- OPTIMIZATION: Use the static runtime
+    let results = rt.block_on(run_search(query, limit, where_clause, nprobes, &mode));
+
+    match results {
+        Ok(found_cards) => Ok(serde_json::to_string(&found_cards).unwrap()),
         Err(err_msg) => Ok(json!({ "status": "error", "message": err_msg }).to_string())
     }
 }
 
+// --- TOOL-CALLING (for driving this engine from an LLM agent) ---
+
+/// OpenAI-style function/tool definitions for every capability this crate
+/// exposes. An agent loop can hand this straight to a model's `tools`
+/// parameter.
+#[pyfunction]
+fn list_tools() -> PyResult<String> {
+    let tools = json!([
+        {
+            "type": "function",
+            "function": {
+                "name": "search_cards",
+                "description": "Semantic + filtered search over the card index (the Librarian).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "query": { "type": "string", "description": "Natural-language search text." },
+                        "limit": { "type": "integer", "description": "Max results to return. Defaults to 5." },
+                        "where_clause": { "type": "string", "description": "Optional SQL filter, e.g. \"type_line LIKE '%Creature%'\"." },
+                        "nprobes": { "type": "integer", "description": "IVF_PQ partitions to probe; higher recalls more at the cost of latency." },
+                        "search_mode": { "type": "string", "enum": ["vector", "fts", "hybrid"], "description": "Defaults to 'hybrid' (vector + keyword, fused with RRF)." }
+                    },
+                    "required": ["query"]
+                }
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "check_board_state",
+                "description": "Runs the rules engine (the Judge) against a GameState and returns the ruling list.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "game_state": { "type": "object", "description": "The full GameState payload." }
+                    },
+                    "required": ["game_state"]
+                }
+            }
+        }
+    ]);
+
+    Ok(tools.to_string())
+}
+
+fn dispatch_search_cards(args: &serde_json::Value) -> Result<serde_json::Value, String> {
+    let query = args
+        .get("query")
+        .and_then(serde_json::Value::as_str)
+        .ok_or("Missing required string field 'query'")?
+        .to_string();
+    let limit = args.get("limit").and_then(serde_json::Value::as_u64).map(|n| n as usize).unwrap_or(5);
+    let where_clause = args.get("where_clause").and_then(serde_json::Value::as_str).map(|s| s.to_string());
+    let nprobes = args.get("nprobes").and_then(serde_json::Value::as_u64).map(|n| n as usize);
+    let mode = args.get("search_mode").and_then(serde_json::Value::as_str).unwrap_or("hybrid").to_string();
+
+    let found_cards = get_runtime().block_on(run_search(query, limit, where_clause, nprobes, &mode))?;
+    Ok(serde_json::Value::Array(found_cards))
+}
+
+fn dispatch_check_board_state(args: &serde_json::Value) -> Result<serde_json::Value, String> {
+    let game_state = args
+        .get("game_state")
+        .filter(|v| v.is_object())
+        .ok_or("Missing required object field 'game_state'")?;
+    let response = judge(&game_state.to_string());
+    serde_json::from_str(&response).map_err(|e| e.to_string())
+}
+
+/// Looks up `name` and checks `arguments_json` has the fields that tool's
+/// schema (from `list_tools`) marks `required`, rejecting the call with an
+/// error envelope if one's missing or the wrong type — this is a field-level
+/// presence/type check, not full JSON Schema validation. On success it
+/// invokes the matching function and returns a `{ "tool": ..., "result": ...
+/// }` envelope (or `{ "tool": ..., "error": ... }` on failure). Cheap and
+/// reentrant — it reuses the global `MODEL`/`RUNTIME` singletons just like
+/// `search_cards` does — so a host loop can call it once per tool call a
+/// model requests and keep feeding results back in until the model stops
+/// asking for tools.
+#[pyfunction]
+fn call_tool(name: String, arguments_json: String) -> PyResult<String> {
+    let args: serde_json::Value = match serde_json::from_str(&arguments_json) {
+        Ok(v) => v,
+        Err(e) => return Ok(json!({ "tool": name, "error": format!("Invalid arguments JSON: {}", e) }).to_string()),
+    };
+
+    let result = match name.as_str() {
+        "search_cards" => dispatch_search_cards(&args),
+        "check_board_state" => dispatch_check_board_state(&args),
+        other => Err(format!("Unknown tool '{}'", other)),
+    };
+
+    let envelope = match result {
+        Ok(value) => json!({ "tool": name, "result": value }),
+        Err(e) => json!({ "tool": name, "error": e }),
+    };
+
+    Ok(envelope.to_string())
+}
+
 #[pymodule]
 fn mtg_logic_core(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(check_board_state, m)?)?;
     m.add_function(wrap_pyfunction!(search_cards, m)?)?;
+    m.add_function(wrap_pyfunction!(list_tools, m)?)?;
+    m.add_function(wrap_pyfunction!(call_tool, m)?)?;
     Ok(())
 }
+
+// --- TESTS ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(id: &str) -> serde_json::Value {
+        json!({ "id": id, "name": id, "type": "Instant", "text": "" })
+    }
+
+    #[test]
+    fn test_rrf_ranks_item_in_both_lists_above_single_list_item() {
+        // "bolt" is the top vector hit and also shows up in the fts list;
+        // "strike" only ever appears in the vector list. RRF should still
+        // put "bolt" first since its score is a sum across both lists.
+        let vector_list = vec![card("bolt"), card("strike")];
+        let fts_list = vec![card("bolt"), card("unrelated")];
+
+        let fused = reciprocal_rank_fusion(vec![vector_list, fts_list], 10);
+
+        assert_eq!(fused[0]["id"], "bolt");
+        assert!(fused[0]["score"].as_f64().unwrap() > fused[1]["score"].as_f64().unwrap());
+    }
+
+    #[test]
+    fn test_rrf_is_sorted_descending_and_respects_limit() {
+        let vector_list = vec![card("a"), card("b"), card("c"), card("d")];
+        let fused = reciprocal_rank_fusion(vec![vector_list], 2);
+
+        assert_eq!(fused.len(), 2);
+        assert_eq!(fused[0]["id"], "a");
+        assert_eq!(fused[1]["id"], "b");
+
+        let scores: Vec<f64> = fused.iter().map(|c| c["score"].as_f64().unwrap()).collect();
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+    }
+
+    #[test]
+    fn test_rrf_keeps_single_list_contribution() {
+        // A document present in only one list still gets a score and a slot.
+        let fused = reciprocal_rank_fusion(vec![vec![card("only")]], 10);
+        assert_eq!(fused.len(), 1);
+        assert_eq!(fused[0]["id"], "only");
+        assert!(fused[0]["score"].as_f64().unwrap() > 0.0);
+    }
+}